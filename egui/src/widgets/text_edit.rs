@@ -1,11 +1,192 @@
 use crate::{paint::*, *};
+use unicode_segmentation::UnicodeSegmentation;
 
-#[derive(Clone, Copy, Debug, Default)]
+/// The maximum number of edits kept on the undo (and redo) stack, per `TextEdit`.
+const MAX_UNDO_DEPTH: usize = 100;
+
+/// Number of grapheme clusters in `s`. This is what `State::cursor` counts in,
+/// so that e.g. a flag emoji or a modifier-combined emoji moves as one unit.
+fn grapheme_count(s: &str) -> usize {
+    s.graphemes(true).count()
+}
+
+/// `Galley::char_at`/`Galley::char_start_pos` index `s` by `char`, not by
+/// grapheme cluster, so any index coming from or going to a `Galley` has to
+/// be translated at the boundary. Converts a char index into `s` into a
+/// grapheme-cluster index into the same string.
+fn char_idx_to_grapheme_idx(s: &str, char_idx: usize) -> usize {
+    let byte_idx = s.char_indices().nth(char_idx).map_or(s.len(), |(b, _)| b);
+    if byte_idx >= s.len() {
+        return grapheme_count(s);
+    }
+    // `byte_idx` may land in the middle of a cluster (e.g. a char index that
+    // points at the second codepoint of a flag emoji); snap to the index of
+    // the grapheme it falls inside, rather than the next one.
+    s.grapheme_indices(true)
+        .take_while(|(b, _)| *b <= byte_idx)
+        .count()
+        - 1
+}
+
+/// The inverse of [`char_idx_to_grapheme_idx`]: converts a grapheme-cluster
+/// index into `s` into the char index that `Galley::char_start_pos` expects.
+fn grapheme_idx_to_char_idx(s: &str, grapheme_idx: usize) -> usize {
+    let byte_idx = s
+        .grapheme_indices(true)
+        .nth(grapheme_idx)
+        .map_or(s.len(), |(b, _)| b);
+    s[..byte_idx].chars().count()
+}
+
+/// How the text cursor (caret) of a `TextEdit` is drawn. Read from
+/// `ui.style().visuals.text_cursor_shape`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub enum TextCursorShape {
+    /// A thin vertical line, like most text editors. This is the default.
+    Beam,
+    /// A filled rect covering the whole cell of the character at the cursor,
+    /// with the character re-drawn on top in an inverted color.
+    Block,
+    /// A thin filled rect along the bottom of the cell.
+    Underline,
+    /// Like `Block`, but only the outline is drawn.
+    HollowBlock,
+}
+
+impl Default for TextCursorShape {
+    fn default() -> Self {
+        TextCursorShape::Beam
+    }
+}
+
+/// How a multiline `TextEdit` breaks its text into lines.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TextWrap {
+    /// Break at whitespace, as close to the available width as possible. The default.
+    Whitespace,
+    /// Break at grapheme-cluster boundaries, ignoring whitespace. Good for
+    /// code-like content and CJK text, where whitespace wrapping looks wrong.
+    Character,
+    /// Never break; lines overflow the available width horizontally.
+    None,
+}
+
+impl Default for TextWrap {
+    fn default() -> Self {
+        TextWrap::Whitespace
+    }
+}
+
+/// Lays out `text` for a multiline `TextEdit`, honoring the chosen wrap mode.
+fn layout_multiline_wrapped(
+    font: &Font,
+    text: String,
+    available_width: f32,
+    wrap: TextWrap,
+) -> Galley {
+    match wrap {
+        TextWrap::Whitespace => font.layout_multiline(text, available_width),
+        TextWrap::Character => {
+            let wrapped: String = text
+                .split('\n')
+                .map(|line| wrap_line_by_character(font, line, available_width))
+                .collect::<Vec<_>>()
+                .join("\n");
+            // The text is already broken at grapheme boundaries above, so don't
+            // let `layout_multiline` additionally re-wrap it at whitespace.
+            font.layout_multiline(wrapped, f32::INFINITY)
+        }
+        TextWrap::None => font.layout_multiline(text, f32::INFINITY),
+    }
+}
+
+/// Breaks a single line (no `\n`) into several, inserting `\n` at the last
+/// grapheme boundary that still fits within `available_width`. Used by
+/// `TextWrap::Character`, since there's no font/paint-layer equivalent of
+/// `layout_multiline`'s whitespace wrapping that wraps per grapheme instead.
+fn wrap_line_by_character(font: &Font, line: &str, available_width: f32) -> String {
+    if !available_width.is_finite() || available_width <= 0.0 {
+        return line.to_owned();
+    }
+
+    let galley = font.layout_single_line(line.to_owned());
+    let graphemes: Vec<&str> = line.graphemes(true).collect();
+    // `galley` is indexed by `char`, not by grapheme cluster, so every
+    // grapheme index `i` below has to be translated before it's used to
+    // query `char_start_pos` — otherwise a multi-codepoint cluster (flag
+    // emoji, combining marks, most CJK punctuation pairs) measures the x
+    // position of the wrong glyph and produces a mid-cluster wrap point.
+    let char_idx_at = |grapheme_idx: usize| grapheme_idx_to_char_idx(line, grapheme_idx);
+    let mut wrapped = String::new();
+    let mut segment_start = 0;
+    let mut segment_start_x = 0.0;
+
+    for i in 0..graphemes.len() {
+        let width = galley.char_start_pos(char_idx_at(i + 1)).x - segment_start_x;
+        if width > available_width && i > segment_start {
+            wrapped.push_str(&graphemes[segment_start..i].concat());
+            wrapped.push('\n');
+            segment_start_x = galley.char_start_pos(char_idx_at(i)).x;
+            segment_start = i;
+        }
+    }
+    wrapped.push_str(&graphemes[segment_start..].concat());
+    wrapped
+}
+
+/// A single reversible edit: the grapheme clusters in `removed` that used to
+/// occupy grapheme indices `start..start + grapheme_count(&removed)` were
+/// replaced by `inserted`.
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+struct Edit {
+    start: usize,
+    removed: String,
+    inserted: String,
+    cursor_before: usize,
+    cursor_after: usize,
+}
+
+#[derive(Clone, Debug, Default)]
 #[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
 pub(crate) struct State {
-    /// Character based, NOT bytes.
+    /// Grapheme-cluster based, NOT chars and NOT bytes, so the cursor never
+    /// lands in the middle of an emoji, flag, or combining mark.
     /// TODO: store as line + row
     pub cursor: Option<usize>,
+
+    /// The other end of the selection, if any. The selected range is the
+    /// (possibly empty) span of grapheme clusters between `select_origin` and `cursor`.
+    pub select_origin: Option<usize>,
+
+    /// Edit history, most recent last. `Ctrl+Z` pops from here onto `redo_stack`.
+    undo_stack: Vec<Edit>,
+    /// Undone edits, most recent last. `Ctrl+Shift+Z` / `Ctrl+Y` pops from here onto `undo_stack`.
+    redo_stack: Vec<Edit>,
+    /// Set whenever the cursor moves without an edit (arrow keys, Home/End,
+    /// mouse click/drag). `push_edit` checks this so it doesn't coalesce an
+    /// edit into the previous transaction just because the two happen to be
+    /// at contiguous offsets, if the cursor wandered off and back in between.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    cursor_moved_since_edit: bool,
+}
+
+impl State {
+    /// The selected grapheme-cluster range, as `(start, end)` with `start < end`, if any.
+    fn selection(&self) -> Option<(usize, usize)> {
+        selected_range(self.cursor?, self.select_origin)
+    }
+}
+
+/// The selected grapheme-cluster range, as `(start, end)` with `start < end`, if any.
+fn selected_range(cursor: usize, select_origin: Option<usize>) -> Option<(usize, usize)> {
+    let origin = select_origin?;
+    if origin == cursor {
+        None
+    } else {
+        Some((origin.min(cursor), origin.max(cursor)))
+    }
 }
 
 /// A text region that the user can edit the contents of.
@@ -29,6 +210,8 @@ pub struct TextEdit<'t> {
     text_color: Option<Srgba>,
     multiline: bool,
     enabled: bool,
+    password: bool,
+    wrap: TextWrap,
     desired_width: Option<f32>,
     desired_height_rows: usize,
 }
@@ -49,6 +232,8 @@ impl<'t> TextEdit<'t> {
             text_color: None,
             multiline: false,
             enabled: true,
+            password: false,
+            wrap: TextWrap::Whitespace,
             desired_width: None,
             desired_height_rows: 1,
         }
@@ -64,6 +249,8 @@ impl<'t> TextEdit<'t> {
             text_color: None,
             multiline: true,
             enabled: true,
+            password: false,
+            wrap: TextWrap::Whitespace,
             desired_width: None,
             desired_height_rows: 4,
         }
@@ -100,6 +287,21 @@ impl<'t> TextEdit<'t> {
         self
     }
 
+    /// Show a masked `•` per grapheme instead of the real text, for password fields.
+    /// Editing, cursor movement, and selection still operate on the real text.
+    /// Copy and cut never put the text on the clipboard (cut still deletes it).
+    pub fn password(mut self, password: bool) -> Self {
+        self.password = password;
+        self
+    }
+
+    /// How a multiline `TextEdit` breaks its text into lines. Ignored for `singleline`.
+    /// Default is `TextWrap::Whitespace`.
+    pub fn wrap(mut self, wrap: TextWrap) -> Self {
+        self.wrap = wrap;
+        self
+    }
+
     /// Set to 0.0 to keep as small as possible
     pub fn desired_width(mut self, desired_width: f32) -> Self {
         self.desired_width = Some(desired_width);
@@ -125,6 +327,8 @@ impl<'t> Widget for TextEdit<'t> {
             text_color,
             multiline,
             enabled,
+            password,
+            wrap,
             desired_width,
             desired_height_rows,
         } = self;
@@ -144,10 +348,15 @@ impl<'t> Widget for TextEdit<'t> {
         let font = &ui.fonts()[text_style];
         let line_spacing = font.line_spacing();
         let available_width = ui.available().width();
+        // The text the `Galley` is laid out from. `Galley::char_at` /
+        // `char_start_pos` index into it by `char`, not by grapheme cluster,
+        // so every use of them below goes through `char_idx_to_grapheme_idx` /
+        // `grapheme_idx_to_char_idx` to translate against this string.
+        let mut display_text = mask_if_password(password, text);
         let mut galley = if multiline {
-            font.layout_multiline(text.clone(), available_width)
+            layout_multiline_wrapped(font, display_text.clone(), available_width, wrap)
         } else {
-            font.layout_single_line(text.clone())
+            font.layout_single_line(display_text.clone())
         };
 
         let desired_width = desired_width.unwrap_or_else(|| ui.style().spacing.text_edit_width);
@@ -162,12 +371,27 @@ impl<'t> Widget for TextEdit<'t> {
         } else {
             Sense::nothing()
         };
-        let response = ui.interact(rect, id, sense); // TODO: implement drag-select
+        let response = ui.interact(rect, id, sense);
 
         if response.clicked && enabled {
             ui.memory().request_kb_focus(id);
             if let Some(mouse_pos) = ui.input().mouse.pos {
-                state.cursor = Some(galley.char_at(mouse_pos - response.rect.min).char_idx);
+                let char_idx = galley.char_at(mouse_pos - response.rect.min).char_idx;
+                let cursor_idx = char_idx_to_grapheme_idx(&display_text, char_idx);
+                state.cursor = Some(cursor_idx);
+                state.select_origin = Some(cursor_idx);
+                state.cursor_moved_since_edit = true;
+            }
+        } else if response.dragged && enabled {
+            // Extend the selection as the mouse is dragged, keeping the press
+            // position as the fixed anchor.
+            if let Some(mouse_pos) = ui.input().mouse.pos {
+                if state.select_origin.is_none() {
+                    state.select_origin = state.cursor;
+                }
+                let char_idx = galley.char_at(mouse_pos - response.rect.min).char_idx;
+                state.cursor = Some(char_idx_to_grapheme_idx(&display_text, char_idx));
+                state.cursor_moved_since_edit = true;
             }
         } else if ui.input().mouse.click || (ui.input().mouse.pressed && !response.hovered) {
             // User clicked somewhere else
@@ -182,19 +406,62 @@ impl<'t> Widget for TextEdit<'t> {
         }
 
         if ui.memory().has_kb_focus(id) && enabled {
-            let mut cursor = state.cursor.unwrap_or_else(|| text.chars().count());
-            cursor = clamp(cursor, 0..=text.chars().count());
+            let mut cursor = state.cursor.unwrap_or_else(|| grapheme_count(text));
+            cursor = clamp(cursor, 0..=grapheme_count(text));
+            let mut select_origin = state.select_origin;
 
             for event in &ui.input().events {
                 match event {
-                    Event::Copy | Event::Cut => {
-                        // TODO: cut
-                        ui.ctx().output().copied_text = text.clone();
+                    Event::Copy => {
+                        if !password {
+                            ui.ctx().output().copied_text =
+                                match selected_range(cursor, select_origin) {
+                                    Some((start, end)) => substring(text, start, end),
+                                    None => text.clone(),
+                                };
+                        }
+                    }
+                    Event::Cut => {
+                        let removed = match selected_range(cursor, select_origin) {
+                            Some((start, end)) => {
+                                replace_range(&mut state, &mut cursor, text, start, end, "")
+                            }
+                            None => {
+                                let len = grapheme_count(text);
+                                replace_range(&mut state, &mut cursor, text, 0, len, "")
+                            }
+                        };
+                        // Don't leak the secret onto the clipboard in password mode.
+                        if !password {
+                            ui.ctx().output().copied_text = removed;
+                        }
+                        select_origin = None;
+                    }
+                    // `Paste` is part of the same externally-defined `Event` enum as
+                    // `Copy`/`Cut`/`Text`/`Key` above (this file doesn't define or
+                    // re-export `Event`); unlike those, it wasn't already in use
+                    // elsewhere in this file before this change.
+                    Event::Paste(text_to_insert) => {
+                        if !text_to_insert.is_empty() {
+                            replace_selection(
+                                &mut state,
+                                &mut cursor,
+                                &mut select_origin,
+                                text,
+                                text_to_insert,
+                            );
+                        }
                     }
                     Event::Text(text_to_insert) => {
                         // newlines are handled by `Key::Enter`.
                         if text_to_insert != "\n" && text_to_insert != "\r" {
-                            insert_text(&mut cursor, text, text_to_insert);
+                            replace_selection(
+                                &mut state,
+                                &mut cursor,
+                                &mut select_origin,
+                                text,
+                                text_to_insert,
+                            );
                         }
                     }
                     Event::Key {
@@ -202,7 +469,13 @@ impl<'t> Widget for TextEdit<'t> {
                         pressed: true,
                     } => {
                         if multiline {
-                            insert_text(&mut cursor, text, "\n");
+                            replace_selection(
+                                &mut state,
+                                &mut cursor,
+                                &mut select_origin,
+                                text,
+                                "\n",
+                            );
                         } else {
                             // Common to end input with enter
                             ui.memory().surrender_kb_focus(id);
@@ -216,20 +489,48 @@ impl<'t> Widget for TextEdit<'t> {
                         ui.memory().surrender_kb_focus(id);
                         break;
                     }
+                    Event::Key {
+                        key: Key::Z,
+                        pressed: true,
+                    } if ui.input().modifiers.ctrl => {
+                        if ui.input().modifiers.shift {
+                            redo(&mut state, &mut cursor, text);
+                        } else {
+                            undo(&mut state, &mut cursor, text);
+                        }
+                        select_origin = None;
+                    }
+                    Event::Key {
+                        key: Key::Y,
+                        pressed: true,
+                    } if ui.input().modifiers.ctrl => {
+                        redo(&mut state, &mut cursor, text);
+                        select_origin = None;
+                    }
                     Event::Key { key, pressed: true } => {
-                        on_key_press(&mut cursor, text, *key);
+                        on_key_press(
+                            &mut state,
+                            &mut cursor,
+                            &mut select_origin,
+                            text,
+                            *key,
+                            ui.input().modifiers.shift,
+                            ui.input().modifiers.ctrl,
+                        );
                     }
                     _ => {}
                 }
             }
             state.cursor = Some(cursor);
+            state.select_origin = select_origin;
 
             // layout again to avoid frame delay:
             let font = &ui.fonts()[text_style];
+            display_text = mask_if_password(password, text);
             galley = if multiline {
-                font.layout_multiline(text.clone(), available_width)
+                layout_multiline_wrapped(font, display_text.clone(), available_width, wrap)
             } else {
-                font.layout_single_line(text.clone())
+                font.layout_single_line(display_text.clone())
             };
 
             // dbg!(&galley);
@@ -249,6 +550,18 @@ impl<'t> Widget for TextEdit<'t> {
             });
         }
 
+        if let Some((start, end)) = state.selection() {
+            let selection_color = ui.style().visuals.selection.bg_fill;
+            for rect in selection_rects(&display_text, &galley, line_spacing, start, end) {
+                painter.add(PaintCmd::Rect {
+                    rect: rect.translate(response.rect.min.to_vec2()),
+                    corner_radius: 0.0,
+                    fill: selection_color,
+                    stroke: Default::default(),
+                });
+            }
+        }
+
         if ui.memory().has_kb_focus(id) {
             let cursor_blink_hz = ui.style().visuals.cursor_blink_hz;
             let show_cursor = if 0.0 < cursor_blink_hz {
@@ -260,11 +573,81 @@ impl<'t> Widget for TextEdit<'t> {
 
             if show_cursor {
                 if let Some(cursor) = state.cursor {
-                    let cursor_pos = response.rect.min + galley.char_start_pos(cursor);
-                    painter.line_segment(
-                        [cursor_pos, cursor_pos + vec2(0.0, line_spacing)],
-                        (ui.style().visuals.text_cursor_width, color::WHITE),
-                    );
+                    // `cursor` is a grapheme index; `char_start_pos` wants a char
+                    // index into `display_text`, the string the galley was built from.
+                    let cursor_char_idx = grapheme_idx_to_char_idx(&display_text, cursor);
+                    let cursor_pos = response.rect.min + galley.char_start_pos(cursor_char_idx);
+                    let cursor_width = ui.style().visuals.text_cursor_width;
+                    let cursor_color = color::WHITE;
+
+                    match ui.style().visuals.text_cursor_shape {
+                        TextCursorShape::Beam => {
+                            painter.line_segment(
+                                [cursor_pos, cursor_pos + vec2(0.0, line_spacing)],
+                                (cursor_width, cursor_color),
+                            );
+                        }
+                        shape @ TextCursorShape::Block
+                        | shape @ TextCursorShape::Underline
+                        | shape @ TextCursorShape::HollowBlock => {
+                            let same_line = cursor_and_next_on_same_line(&display_text, cursor);
+                            let cell_width = if same_line {
+                                // `cursor`/`cursor + 1` are grapheme indices; `char_start_pos`
+                                // wants char indices into `display_text`.
+                                let next_char_idx =
+                                    grapheme_idx_to_char_idx(&display_text, cursor + 1);
+                                galley.char_start_pos(next_char_idx).x
+                                    - galley.char_start_pos(cursor_char_idx).x
+                            } else {
+                                line_spacing * 0.5
+                            };
+                            let cell_rect =
+                                Rect::from_min_size(cursor_pos, vec2(cell_width, line_spacing));
+
+                            match shape {
+                                TextCursorShape::Block => {
+                                    painter.add(PaintCmd::Rect {
+                                        rect: cell_rect,
+                                        corner_radius: 0.0,
+                                        fill: cursor_color,
+                                        stroke: Default::default(),
+                                    });
+                                    if let Some(glyph) = display_text.graphemes(true).nth(cursor) {
+                                        let font = &ui.fonts()[text_style];
+                                        let glyph_galley =
+                                            font.layout_single_line(glyph.to_owned());
+                                        painter.galley(
+                                            cursor_pos,
+                                            glyph_galley,
+                                            text_style,
+                                            ui.style().visuals.dark_bg_color,
+                                        );
+                                    }
+                                }
+                                TextCursorShape::Underline => {
+                                    let underline_rect = Rect::from_min_max(
+                                        pos2(cell_rect.min.x, cell_rect.max.y - cursor_width),
+                                        cell_rect.max,
+                                    );
+                                    painter.add(PaintCmd::Rect {
+                                        rect: underline_rect,
+                                        corner_radius: 0.0,
+                                        fill: cursor_color,
+                                        stroke: Default::default(),
+                                    });
+                                }
+                                TextCursorShape::HollowBlock => {
+                                    painter.add(PaintCmd::Rect {
+                                        rect: cell_rect,
+                                        corner_radius: 0.0,
+                                        fill: Default::default(),
+                                        stroke: (cursor_width, cursor_color).into(),
+                                    });
+                                }
+                                TextCursorShape::Beam => unreachable!(),
+                            }
+                        }
+                    }
                 }
             }
         }
@@ -282,72 +665,360 @@ impl<'t> Widget for TextEdit<'t> {
     }
 }
 
-fn insert_text(cursor: &mut usize, text: &mut String, text_to_insert: &str) {
-    // eprintln!("insert_text {:?}", text_to_insert);
+/// One filled rect per line covered by the `[start, end)` grapheme-cluster range, in
+/// widget-local coordinates (i.e. relative to the galley's origin).
+fn selection_rects(
+    display_text: &str,
+    galley: &Galley,
+    line_spacing: f32,
+    start: usize,
+    end: usize,
+) -> Vec<Rect> {
+    let mut rects = Vec::new();
+    let mut line_start = start;
+    while line_start < end {
+        let pos = line_col_from_grapheme_idx(display_text, line_start);
+        let line = line_from_number(display_text, pos.0);
+        let line_end =
+            grapheme_idx_from_line_col(display_text, (pos.0, grapheme_count(line))).min(end);
+
+        // `line_start`/`line_end` are grapheme indices; `char_start_pos` wants
+        // char indices into `display_text`.
+        let min_pos = galley.char_start_pos(grapheme_idx_to_char_idx(display_text, line_start));
+        let max_pos = galley.char_start_pos(grapheme_idx_to_char_idx(display_text, line_end));
+        rects.push(Rect::from_min_max(
+            min_pos,
+            pos2(max_pos.x, min_pos.y + line_spacing),
+        ));
+
+        // Step past the line break onto the next line.
+        line_start = line_end + 1;
+    }
+    rects
+}
+
+/// Whether the grapheme at `cursor` and the one right after it (`cursor + 1`)
+/// fall on the same `\n`-separated line of `text`; rows produced by wrapping
+/// are not taken into account. `char_start_pos(cursor + 1)` is
+/// only meaningful as the *end* of the cursor's cell if that holds — if
+/// `cursor` is at the end of a non-final line (or the next grapheme is a
+/// `\n`), `char_start_pos(cursor + 1)` resolves to the start of the next
+/// line instead, which would make a naive width delta negative.
+///
+/// (Comparing `line_col_from_grapheme_idx(cursor)` and `(cursor + 1)` isn't
+/// enough here: that function attributes the `\n` itself to the line it
+/// ends, not the one it starts, so a cursor right before a `\n` would
+/// wrongly compare equal.)
+fn cursor_and_next_on_same_line(text: &str, cursor: usize) -> bool {
+    let graphemes: Vec<&str> = text.graphemes(true).collect();
+    if cursor + 1 > graphemes.len() {
+        return false;
+    }
+    graphemes.get(cursor) != Some(&"\n") && graphemes.get(cursor + 1) != Some(&"\n")
+}
+
+/// Returns `text` unchanged, or, if `password` is set, a same-length string of
+/// `•` (one per grapheme cluster) for layout/painting purposes only. Newlines
+/// are kept verbatim so line/column math (`Home`/`End`/`Up`/`Down`,
+/// `selection_rects`) still lines up with the real, unmasked text.
+fn mask_if_password(password: bool, text: &str) -> String {
+    if password {
+        text.graphemes(true)
+            .map(|g| if g == "\n" { "\n" } else { "•" })
+            .collect()
+    } else {
+        text.to_owned()
+    }
+}
+
+fn substring(text: &str, start: usize, end: usize) -> String {
+    text.graphemes(true).skip(start).take(end - start).collect()
+}
+
+/// Replaces the current selection (if any) with `inserted`, or just inserts
+/// at the cursor if nothing is selected. Clears the selection and records an
+/// undoable edit.
+fn replace_selection(
+    state: &mut State,
+    cursor: &mut usize,
+    select_origin: &mut Option<usize>,
+    text: &mut String,
+    inserted: &str,
+) {
+    let (start, end) = selected_range(*cursor, *select_origin).unwrap_or((*cursor, *cursor));
+    replace_range(state, cursor, text, start, end, inserted);
+    *select_origin = None;
+}
+
+/// Replaces `text[start..end]` (grapheme-indexed) with `inserted`, moves `cursor`
+/// to just after the inserted text, and pushes the inverse onto the undo
+/// stack (clearing the redo stack). Returns the text that was removed.
+fn replace_range(
+    state: &mut State,
+    cursor: &mut usize,
+    text: &mut String,
+    start: usize,
+    end: usize,
+    inserted: &str,
+) -> String {
+    let cursor_before = *cursor;
+    let removed = remove_grapheme_range(text, start, end);
+    insert_str_at(text, start, inserted);
+    *cursor = start + grapheme_count(inserted);
+
+    push_edit(
+        state,
+        Edit {
+            start,
+            removed: removed.clone(),
+            inserted: inserted.to_owned(),
+            cursor_before,
+            cursor_after: *cursor,
+        },
+    );
+
+    removed
+}
+
+/// Pushes `edit` onto the undo stack, clearing the redo stack, unless it can
+/// be merged into the previous entry (consecutive non-whitespace single-char
+/// insertions are coalesced into one transaction, so undo doesn't take a full
+/// keystroke's worth of key presses to step through a typed word). A merge is
+/// also refused if the cursor moved away and back since the previous edit,
+/// even if the offsets still line up, since that should start a new
+/// transaction boundary.
+fn push_edit(state: &mut State, edit: Edit) {
+    state.redo_stack.clear();
+
+    if let Some(last) = state.undo_stack.last_mut() {
+        let is_single_grapheme_insert =
+            |e: &Edit| e.removed.is_empty() && grapheme_count(&e.inserted) == 1;
+        // `last` may already hold several coalesced graphemes, so it only has
+        // to be a whitespace-free pure insertion to keep growing.
+        let mergeable = !state.cursor_moved_since_edit
+            && last.removed.is_empty()
+            && !last.inserted.is_empty()
+            && !last.inserted.chars().any(char::is_whitespace)
+            && is_single_grapheme_insert(&edit)
+            && !edit.inserted.chars().next().unwrap().is_whitespace()
+            && edit.start == last.start + grapheme_count(&last.inserted);
+        if mergeable {
+            last.inserted.push_str(&edit.inserted);
+            last.cursor_after = edit.cursor_after;
+            state.cursor_moved_since_edit = false;
+            return;
+        }
+    }
+
+    state.undo_stack.push(edit);
+    if state.undo_stack.len() > MAX_UNDO_DEPTH {
+        state.undo_stack.remove(0);
+    }
+    state.cursor_moved_since_edit = false;
+}
+
+fn undo(state: &mut State, cursor: &mut usize, text: &mut String) {
+    if let Some(edit) = state.undo_stack.pop() {
+        let end = edit.start + grapheme_count(&edit.inserted);
+        remove_grapheme_range(text, edit.start, end);
+        insert_str_at(text, edit.start, &edit.removed);
+        *cursor = edit.cursor_before;
+        state.redo_stack.push(edit);
+    }
+}
 
-    let mut char_it = text.chars();
-    let mut new_text = String::with_capacity(text.capacity());
-    for _ in 0..*cursor {
-        let c = char_it.next().unwrap();
-        new_text.push(c);
+fn redo(state: &mut State, cursor: &mut usize, text: &mut String) {
+    if let Some(edit) = state.redo_stack.pop() {
+        let end = edit.start + grapheme_count(&edit.removed);
+        remove_grapheme_range(text, edit.start, end);
+        insert_str_at(text, edit.start, &edit.inserted);
+        *cursor = edit.cursor_after;
+        state.undo_stack.push(edit);
+    }
+}
+
+/// Removes the grapheme clusters in `[start, end)` and returns them.
+fn remove_grapheme_range(text: &mut String, start: usize, end: usize) -> String {
+    let removed;
+    let new_text;
+    {
+        let graphemes: Vec<&str> = text.graphemes(true).collect();
+        removed = graphemes[start..end].concat();
+        new_text = [graphemes[..start].concat(), graphemes[end..].concat()].concat();
     }
-    *cursor += text_to_insert.chars().count();
-    new_text += text_to_insert;
-    new_text.extend(char_it);
     *text = new_text;
+    removed
 }
 
-fn on_key_press(cursor: &mut usize, text: &mut String, key: Key) {
-    // eprintln!("on_key_press before: '{}', cursor at {}", text, cursor);
+/// Inserts `s` at grapheme-cluster index `at`, without touching any cursor.
+fn insert_str_at(text: &mut String, at: usize, s: &str) {
+    if s.is_empty() {
+        return;
+    }
+    let mut new_text;
+    {
+        let graphemes: Vec<&str> = text.graphemes(true).collect();
+        new_text = graphemes[..at].concat();
+        new_text.push_str(s);
+        new_text.push_str(&graphemes[at..].concat());
+    }
+    *text = new_text;
+}
 
-    match key {
-        Key::Backspace if *cursor > 0 => {
-            *cursor -= 1;
+/// A class of consecutive characters, for word-wise motion.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum CharClass {
+    Whitespace,
+    Word,
+    Punctuation,
+}
+
+fn char_class(c: char) -> CharClass {
+    if c.is_whitespace() {
+        CharClass::Whitespace
+    } else if c.is_alphanumeric() || c == '_' {
+        CharClass::Word
+    } else {
+        CharClass::Punctuation
+    }
+}
 
-            let mut char_it = text.chars();
-            let mut new_text = String::with_capacity(text.capacity());
-            for _ in 0..*cursor {
-                new_text.push(char_it.next().unwrap())
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum WordDirection {
+    Backward,
+    Forward,
+}
+
+/// Finds the nearest word boundary from `cursor` (a grapheme index) in the
+/// given direction. Forward: skip the class of characters the cursor is
+/// touching, then skip any whitespace beyond it. Backward: skip any
+/// whitespace before the cursor, then the class of characters before that.
+fn find_word_boundary(text: &str, cursor: usize, direction: WordDirection) -> usize {
+    let graphemes: Vec<&str> = text.graphemes(true).collect();
+    let class_at = |i: usize| -> CharClass {
+        graphemes[i]
+            .chars()
+            .next()
+            .map_or(CharClass::Whitespace, char_class)
+    };
+
+    match direction {
+        WordDirection::Forward => {
+            let mut i = cursor;
+            if i >= graphemes.len() {
+                return graphemes.len();
+            }
+            let start_class = class_at(i);
+            while i < graphemes.len() && class_at(i) == start_class {
+                i += 1;
+            }
+            while i < graphemes.len() && class_at(i) == CharClass::Whitespace {
+                i += 1;
+            }
+            i
+        }
+        WordDirection::Backward => {
+            let mut i = cursor;
+            if i == 0 {
+                return 0;
             }
-            new_text.extend(char_it.skip(1));
-            *text = new_text;
-        }
-        Key::Delete => {
-            let mut char_it = text.chars();
-            let mut new_text = String::with_capacity(text.capacity());
-            for _ in 0..*cursor {
-                new_text.push(char_it.next().unwrap())
+            i -= 1;
+            while i > 0 && class_at(i) == CharClass::Whitespace {
+                i -= 1;
             }
-            new_text.extend(char_it.skip(1));
-            *text = new_text;
+            let class = class_at(i);
+            while i > 0 && class_at(i - 1) == class {
+                i -= 1;
+            }
+            i
+        }
+    }
+}
+
+fn on_key_press(
+    state: &mut State,
+    cursor: &mut usize,
+    select_origin: &mut Option<usize>,
+    text: &mut String,
+    key: Key,
+    shift: bool,
+    ctrl: bool,
+) {
+    // eprintln!("on_key_press before: '{}', cursor at {}", text, cursor);
+
+    let is_movement = matches!(
+        key,
+        Key::Left | Key::Right | Key::Up | Key::Down | Key::Home | Key::End
+    );
+    if is_movement {
+        state.cursor_moved_since_edit = true;
+        if shift {
+            if select_origin.is_none() {
+                *select_origin = Some(*cursor);
+            }
+        } else {
+            *select_origin = None;
+        }
+    }
+
+    match key {
+        Key::Backspace if selected_range(*cursor, *select_origin).is_some() => {
+            replace_selection(state, cursor, select_origin, text, "");
+        }
+        Key::Backspace if ctrl && *cursor > 0 => {
+            let at = *cursor;
+            let start = find_word_boundary(text, at, WordDirection::Backward);
+            replace_range(state, cursor, text, start, at, "");
+        }
+        Key::Backspace if *cursor > 0 => {
+            let at = *cursor;
+            replace_range(state, cursor, text, at - 1, at, "");
+        }
+        Key::Delete if selected_range(*cursor, *select_origin).is_some() => {
+            replace_selection(state, cursor, select_origin, text, "");
+        }
+        Key::Delete if ctrl && *cursor < grapheme_count(text) => {
+            let at = *cursor;
+            let end = find_word_boundary(text, at, WordDirection::Forward);
+            replace_range(state, cursor, text, at, end, "");
+        }
+        Key::Delete if *cursor < grapheme_count(text) => {
+            let at = *cursor;
+            replace_range(state, cursor, text, at, at + 1, "");
         }
         Key::Enter => {} // handled earlier
         Key::Home => {
             // To start of paragraph:
-            let pos = line_col_from_char_idx(text, *cursor);
-            *cursor = char_idx_from_line_col(text, (pos.0, 0));
+            let pos = line_col_from_grapheme_idx(text, *cursor);
+            *cursor = grapheme_idx_from_line_col(text, (pos.0, 0));
         }
         Key::End => {
             // To end of paragraph:
-            let pos = line_col_from_char_idx(text, *cursor);
+            let pos = line_col_from_grapheme_idx(text, *cursor);
             let line = line_from_number(text, pos.0);
-            *cursor = char_idx_from_line_col(text, (pos.0, line.chars().count()));
+            *cursor = grapheme_idx_from_line_col(text, (pos.0, grapheme_count(line)));
+        }
+        Key::Left if ctrl => {
+            *cursor = find_word_boundary(text, *cursor, WordDirection::Backward);
         }
         Key::Left if *cursor > 0 => {
             *cursor -= 1;
         }
+        Key::Right if ctrl => {
+            *cursor = find_word_boundary(text, *cursor, WordDirection::Forward);
+        }
         Key::Right => {
-            *cursor = (*cursor + 1).min(text.chars().count());
+            *cursor = (*cursor + 1).min(grapheme_count(text));
         }
         Key::Up => {
-            let mut pos = line_col_from_char_idx(text, *cursor);
+            let mut pos = line_col_from_grapheme_idx(text, *cursor);
             pos.0 = pos.0.saturating_sub(1);
-            *cursor = char_idx_from_line_col(text, pos);
+            *cursor = grapheme_idx_from_line_col(text, pos);
         }
         Key::Down => {
-            let mut pos = line_col_from_char_idx(text, *cursor);
+            let mut pos = line_col_from_grapheme_idx(text, *cursor);
             pos.0 += 1;
-            *cursor = char_idx_from_line_col(text, pos);
+            *cursor = grapheme_idx_from_line_col(text, pos);
         }
         _ => {}
     }
@@ -355,34 +1026,34 @@ fn on_key_press(cursor: &mut usize, text: &mut String, key: Key) {
     // eprintln!("on_key_press after:  '{}', cursor at {}\n", text, cursor);
 }
 
-fn line_col_from_char_idx(s: &str, char_idx: usize) -> (usize, usize) {
-    let mut char_count = 0;
+fn line_col_from_grapheme_idx(s: &str, grapheme_idx: usize) -> (usize, usize) {
+    let mut count = 0;
 
     let mut last_line_nr = 0;
     let mut last_line = s;
     for (line_nr, line) in s.split('\n').enumerate() {
-        let line_width = line.chars().count();
-        if char_idx <= char_count + line_width {
-            return (line_nr, char_idx - char_count);
+        let line_width = grapheme_count(line);
+        if grapheme_idx <= count + line_width {
+            return (line_nr, grapheme_idx - count);
         }
-        char_count += line_width + 1;
+        count += line_width + 1;
         last_line_nr = line_nr;
         last_line = line;
     }
 
     // safe fallback:
-    (last_line_nr, last_line.chars().count())
+    (last_line_nr, grapheme_count(last_line))
 }
 
-fn char_idx_from_line_col(s: &str, pos: (usize, usize)) -> usize {
-    let mut char_count = 0;
+fn grapheme_idx_from_line_col(s: &str, pos: (usize, usize)) -> usize {
+    let mut count = 0;
     for (line_nr, line) in s.split('\n').enumerate() {
         if line_nr == pos.0 {
-            return char_count + pos.1.min(line.chars().count());
+            return count + pos.1.min(grapheme_count(line));
         }
-        char_count += line.chars().count() + 1;
+        count += grapheme_count(line) + 1;
     }
-    char_count
+    count
 }
 
 fn line_from_number(s: &str, desired_line_number: usize) -> &str {
@@ -393,3 +1064,362 @@ fn line_from_number(s: &str, desired_line_number: usize) -> &str {
     }
     s
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grapheme_idx_to_char_idx_is_what_wrap_line_by_character_needs() {
+        // `wrap_line_by_character` walks grapheme indices but queries a
+        // char-indexed `Galley::char_start_pos`; this is the exact
+        // translation it relies on for a line containing a flag emoji (one
+        // grapheme cluster, two `char`s).
+        let line = "lorem \u{1F1EB}\u{1F1F7} ipsum";
+        for (grapheme_idx, char_idx) in [(0, 0), (6, 6), (7, 8), (8, 9), (9, 10)] {
+            assert_eq!(grapheme_idx_to_char_idx(line, grapheme_idx), char_idx);
+        }
+    }
+
+    #[test]
+    fn grapheme_idx_to_char_idx_accounts_for_multi_codepoint_clusters() {
+        // "a", flag (2 chars, 1 grapheme), "b": grapheme indices 0,1,2
+        // correspond to char indices 0,1,3.
+        let s = "a\u{1F1EB}\u{1F1F7}b";
+        assert_eq!(grapheme_idx_to_char_idx(s, 0), 0);
+        assert_eq!(grapheme_idx_to_char_idx(s, 1), 1);
+        assert_eq!(grapheme_idx_to_char_idx(s, 2), 3);
+        // Past the end clamps to the char length.
+        assert_eq!(grapheme_idx_to_char_idx(s, 3), 4);
+    }
+
+    #[test]
+    fn char_idx_to_grapheme_idx_is_the_inverse_mapping() {
+        let s = "a\u{1F1EB}\u{1F1F7}b";
+        assert_eq!(char_idx_to_grapheme_idx(s, 0), 0);
+        // Char index 2 is in the middle of the flag cluster; it should still
+        // map to the grapheme the cluster belongs to, not split it.
+        assert_eq!(char_idx_to_grapheme_idx(s, 1), 1);
+        assert_eq!(char_idx_to_grapheme_idx(s, 2), 1);
+        assert_eq!(char_idx_to_grapheme_idx(s, 3), 2);
+        assert_eq!(char_idx_to_grapheme_idx(s, 4), 3);
+    }
+
+    #[test]
+    fn grapheme_char_idx_roundtrip_with_combining_marks() {
+        // "e" + combining acute accent + "f": one grapheme ("é"), then "f".
+        let s = "e\u{0301}f";
+        for g in 0..=grapheme_count(s) {
+            let c = grapheme_idx_to_char_idx(s, g);
+            assert_eq!(char_idx_to_grapheme_idx(s, c), g);
+        }
+    }
+
+    #[test]
+    fn cursor_and_next_on_same_line_true_within_a_line() {
+        assert!(cursor_and_next_on_same_line("abc", 0));
+        assert!(cursor_and_next_on_same_line("abc", 1));
+    }
+
+    #[test]
+    fn cursor_and_next_on_same_line_false_at_line_end() {
+        // cursor is on the last grapheme of the first line; the next
+        // grapheme (index 3) is the `\n` itself, which starts the next line.
+        assert!(!cursor_and_next_on_same_line("abc\ndef", 2));
+    }
+
+    #[test]
+    fn cursor_and_next_on_same_line_false_at_text_end() {
+        let text = "abc";
+        assert!(!cursor_and_next_on_same_line(text, grapheme_count(text)));
+    }
+
+    #[test]
+    fn cursor_and_next_on_same_line_true_before_multi_codepoint_cluster() {
+        // cursor sits right before a flag emoji grapheme on the same line.
+        let text = "a\u{1F1EB}\u{1F1F7}b";
+        assert!(cursor_and_next_on_same_line(text, 0));
+    }
+
+    #[test]
+    fn grapheme_count_treats_combining_marks_and_flags_as_one_unit() {
+        // "e" + combining acute accent is two `char`s but one grapheme cluster.
+        assert_eq!(grapheme_count("e\u{0301}"), 1);
+        // A regional-indicator flag (France) is two `char`s but one cluster.
+        assert_eq!(grapheme_count("\u{1F1EB}\u{1F1F7}"), 1);
+        assert_eq!(grapheme_count("ab\u{1F1EB}\u{1F1F7}cd"), 5);
+    }
+
+    #[test]
+    fn remove_grapheme_range_never_splits_a_cluster() {
+        let mut text = "a\u{1F1EB}\u{1F1F7}b".to_owned(); // a, flag, b
+        let removed = remove_grapheme_range(&mut text, 1, 2);
+        assert_eq!(removed, "\u{1F1EB}\u{1F1F7}");
+        assert_eq!(text, "ab");
+    }
+
+    #[test]
+    fn insert_str_at_inserts_between_clusters_not_inside_them() {
+        let mut text = "a\u{1F1EB}\u{1F1F7}b".to_owned();
+        insert_str_at(&mut text, 2, "X");
+        assert_eq!(text, "a\u{1F1EB}\u{1F1F7}Xb");
+    }
+
+    #[test]
+    fn find_word_boundary_forward_skips_word_then_trailing_whitespace() {
+        let text = "foo  bar";
+        // From inside "foo", land on the first char of "bar" (past the word
+        // and the whitespace that follows it).
+        assert_eq!(find_word_boundary(text, 1, WordDirection::Forward), 5);
+        // Already at the end: stays at the end.
+        assert_eq!(
+            find_word_boundary(text, text.len(), WordDirection::Forward),
+            text.len()
+        );
+    }
+
+    #[test]
+    fn find_word_boundary_backward_skips_whitespace_then_word() {
+        let text = "foo  bar";
+        // From inside "bar", land on the start of "bar".
+        assert_eq!(find_word_boundary(text, 7, WordDirection::Backward), 5);
+        // From the start of "bar", skip the gap and land on the start of "foo".
+        assert_eq!(find_word_boundary(text, 5, WordDirection::Backward), 0);
+        // Already at the start: stays at the start.
+        assert_eq!(find_word_boundary(text, 0, WordDirection::Backward), 0);
+    }
+
+    #[test]
+    fn find_word_boundary_stops_at_punctuation_class_change() {
+        // "foo" (word) then "::" (punctuation) then "bar" (word) are three
+        // distinct classes, so forward motion from inside "foo" stops right
+        // after it, not past the punctuation too.
+        let text = "foo::bar";
+        assert_eq!(find_word_boundary(text, 1, WordDirection::Forward), 3);
+    }
+
+    #[test]
+    fn push_edit_merges_consecutive_single_grapheme_insertions() {
+        let mut state = State::default();
+        push_edit(
+            &mut state,
+            Edit {
+                start: 0,
+                removed: String::new(),
+                inserted: "a".to_owned(),
+                cursor_before: 0,
+                cursor_after: 1,
+            },
+        );
+        push_edit(
+            &mut state,
+            Edit {
+                start: 1,
+                removed: String::new(),
+                inserted: "b".to_owned(),
+                cursor_before: 1,
+                cursor_after: 2,
+            },
+        );
+        assert_eq!(state.undo_stack.len(), 1);
+        assert_eq!(state.undo_stack[0].inserted, "ab");
+    }
+
+    #[test]
+    fn push_edit_does_not_merge_across_whitespace() {
+        let mut state = State::default();
+        push_edit(
+            &mut state,
+            Edit {
+                start: 0,
+                removed: String::new(),
+                inserted: "a".to_owned(),
+                cursor_before: 0,
+                cursor_after: 1,
+            },
+        );
+        push_edit(
+            &mut state,
+            Edit {
+                start: 1,
+                removed: String::new(),
+                inserted: " ".to_owned(),
+                cursor_before: 1,
+                cursor_after: 2,
+            },
+        );
+        assert_eq!(state.undo_stack.len(), 2);
+    }
+
+    #[test]
+    fn push_edit_does_not_merge_noncontiguous_edits() {
+        let mut state = State::default();
+        push_edit(
+            &mut state,
+            Edit {
+                start: 0,
+                removed: String::new(),
+                inserted: "a".to_owned(),
+                cursor_before: 0,
+                cursor_after: 1,
+            },
+        );
+        push_edit(
+            &mut state,
+            Edit {
+                start: 5,
+                removed: String::new(),
+                inserted: "b".to_owned(),
+                cursor_before: 5,
+                cursor_after: 6,
+            },
+        );
+        assert_eq!(state.undo_stack.len(), 2);
+    }
+
+    #[test]
+    fn push_edit_does_not_merge_across_a_cursor_jump() {
+        let mut state = State::default();
+        push_edit(
+            &mut state,
+            Edit {
+                start: 0,
+                removed: String::new(),
+                inserted: "a".to_owned(),
+                cursor_before: 0,
+                cursor_after: 1,
+            },
+        );
+        // The cursor wandered off and back to the exact same contiguous
+        // offset (e.g. arrow-key browsing), which should still start a new
+        // undo transaction even though the offsets still line up.
+        state.cursor_moved_since_edit = true;
+        push_edit(
+            &mut state,
+            Edit {
+                start: 1,
+                removed: String::new(),
+                inserted: "b".to_owned(),
+                cursor_before: 1,
+                cursor_after: 2,
+            },
+        );
+        assert_eq!(state.undo_stack.len(), 2);
+    }
+
+    #[test]
+    fn selected_range_orders_start_before_end_regardless_of_anchor_side() {
+        assert_eq!(selected_range(5, Some(2)), Some((2, 5)));
+        assert_eq!(selected_range(2, Some(5)), Some((2, 5)));
+        assert_eq!(selected_range(3, Some(3)), None);
+        assert_eq!(selected_range(3, None), None);
+    }
+
+    #[test]
+    fn shift_movement_sets_the_anchor_on_first_press_and_keeps_it() {
+        let mut state = State::default();
+        let mut cursor = 3;
+        let mut select_origin = None;
+        let mut text = "hello world".to_owned();
+
+        on_key_press(
+            &mut state,
+            &mut cursor,
+            &mut select_origin,
+            &mut text,
+            Key::Right,
+            /* shift */ true,
+            /* ctrl */ false,
+        );
+        assert_eq!(select_origin, Some(3));
+        assert_eq!(cursor, 4);
+
+        // A second shift-movement extends the cursor but keeps the anchor.
+        on_key_press(
+            &mut state,
+            &mut cursor,
+            &mut select_origin,
+            &mut text,
+            Key::Right,
+            true,
+            false,
+        );
+        assert_eq!(select_origin, Some(3));
+        assert_eq!(cursor, 5);
+    }
+
+    #[test]
+    fn unshifted_movement_collapses_the_selection() {
+        let mut state = State::default();
+        let mut cursor = 3;
+        let mut select_origin = Some(0);
+        let mut text = "hello world".to_owned();
+
+        on_key_press(
+            &mut state,
+            &mut cursor,
+            &mut select_origin,
+            &mut text,
+            Key::Right,
+            /* shift */ false,
+            /* ctrl */ false,
+        );
+        assert_eq!(select_origin, None);
+        assert_eq!(cursor, 4);
+    }
+
+    #[test]
+    fn backspace_deletes_the_selection_instead_of_one_grapheme() {
+        let mut state = State::default();
+        let mut cursor = 5;
+        let mut select_origin = Some(1);
+        let mut text = "hello world".to_owned();
+
+        on_key_press(
+            &mut state,
+            &mut cursor,
+            &mut select_origin,
+            &mut text,
+            Key::Backspace,
+            false,
+            false,
+        );
+        assert_eq!(text, "h world");
+        assert_eq!(cursor, 1);
+        assert_eq!(select_origin, None);
+    }
+
+    #[test]
+    fn delete_deletes_the_selection_instead_of_one_grapheme() {
+        let mut state = State::default();
+        let mut cursor = 1;
+        let mut select_origin = Some(5);
+        let mut text = "hello world".to_owned();
+
+        on_key_press(
+            &mut state,
+            &mut cursor,
+            &mut select_origin,
+            &mut text,
+            Key::Delete,
+            false,
+            false,
+        );
+        assert_eq!(text, "h world");
+        assert_eq!(cursor, 1);
+        assert_eq!(select_origin, None);
+    }
+
+    #[test]
+    fn replace_selection_deletes_then_inserts_and_clears_the_selection() {
+        let mut state = State::default();
+        let mut cursor = 5;
+        let mut select_origin = Some(1);
+        let mut text = "hello world".to_owned();
+
+        replace_selection(&mut state, &mut cursor, &mut select_origin, &mut text, "i");
+        assert_eq!(text, "hi world");
+        assert_eq!(cursor, 2);
+        assert_eq!(select_origin, None);
+    }
+}